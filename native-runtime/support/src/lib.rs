@@ -20,29 +20,52 @@
 extern crate environmental;
 extern crate polkadot_state_machine;
 extern crate polkadot_primitives as primitives;
+extern crate secp256k1;
+extern crate parity_codec as codec;
+
+mod trie;
+mod offchain;
 
 use std::fmt;
 use primitives::ed25519;
+use codec::Decode;
+
+pub use offchain::{RequestId, Timestamp, HttpError, HttpRequestStatus};
 
 pub use std::vec::Vec;
 pub use std::rc::Rc;
 pub use std::cell::RefCell;
 pub use std::boxed::Box;
 pub use std::slice;
-pub use std::mem::{size_of, transmute, swap, uninitialized};
+pub use std::mem::swap;
 
 pub use polkadot_state_machine::Externalities;
 
-// TODO: use the real error, not NoError.
-
-#[derive(Debug)]
-pub struct NoError;
-impl fmt::Display for NoError {
-	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "") }
+/// An error reading or decoding a value from storage.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+	/// There was no value under the given key.
+	NotFound,
+	/// A value was found but could not be decoded as the requested type.
+	DecodeFailed,
+	/// The backend storing state failed to service the request.
+	BackendError,
+}
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Error::NotFound => write!(f, "key not found in storage"),
+			Error::DecodeFailed => write!(f, "value found in storage could not be decoded"),
+			Error::BackendError => write!(f, "storage backend error"),
+		}
+	}
 }
 
-environmental!(ext : Externalities<Error=NoError> + 'static);
+environmental!(ext : Externalities<Error=Error> + 'static);
 
+/// Get the raw bytes stored under `key`, or an empty vector if there's nothing there (or the
+/// backend failed to service the request). Use `storage_decode` when you know the type you
+/// expect back.
 pub fn storage(key: &[u8]) -> Vec<u8> {
 	ext::with(|ext| ext.storage(key).ok().map(|s| s.to_vec()))
 		.unwrap_or(None)
@@ -61,21 +84,15 @@ pub fn read_storage(key: &[u8], value_out: &mut [u8]) -> usize {
 	}).unwrap_or(0)
 }
 
-pub fn storage_into<T: Sized>(_key: &[u8]) -> Option<T> {
-	let size = size_of::<T>();
-
-	ext::with(|ext| {
-		if let Ok(value) = ext.storage(_key) {
-			if value.len() == size {
-				unsafe {
-					let mut result: T = std::mem::uninitialized();
-					std::slice::from_raw_parts_mut(transmute::<*mut T, *mut u8>(&mut result), size)
-						.copy_from_slice(&value);
-					return Some(result);
-				}
-			}
-		}
-		None
+/// Get `key` from storage and SCALE-decode it as `T`, returning `None` if there's no value
+/// there, the backend failed, or the stored bytes don't decode as `T`.
+///
+/// This replaces reinterpreting the raw bytes in-place: that approach was unsound for any type
+/// with invalid bit patterns or padding, since it never validated what was actually stored.
+pub fn storage_decode<T: Decode>(key: &[u8]) -> Option<T> {
+	ext::with(|ext| match ext.storage(key) {
+		Ok(value) => Decode::decode(&mut &value[..]),
+		Err(_) => None,
 	}).unwrap_or(None)
 }
 
@@ -85,6 +102,65 @@ pub fn set_storage(key: &[u8], value: &[u8]) {
 	);
 }
 
+/// Remove `key` from storage.
+pub fn clear_storage(key: &[u8]) {
+	ext::with(|ext|
+		ext.clear_storage(key)
+	);
+}
+
+/// Start a new transactional storage overlay. Writes made after this call land in the overlay
+/// rather than committed storage, and are only applied once the matching `commit_transaction`
+/// runs; `rollback_transaction` discards them instead. Overlays nest: each `start_transaction`
+/// pushes a new layer on top of any already open.
+pub fn start_transaction() {
+	ext::with(|ext| ext.start_transaction());
+}
+
+/// Merge the innermost transactional overlay into the one below it (or into committed storage,
+/// if this was the outermost transaction).
+pub fn commit_transaction() {
+	ext::with(|ext| ext.commit_transaction());
+}
+
+/// Discard the innermost transactional overlay and all writes made within it.
+pub fn rollback_transaction() {
+	ext::with(|ext| ext.rollback_transaction());
+}
+
+/// Get `key` from the child trie denoted by `storage_key`, or an empty vector if there's nothing
+/// there.
+///
+/// `storage_key` is itself a key into the top-level trie, under which the root of the child
+/// trie is stored; this makes every child trie an independent sub-namespace of the top-level
+/// state while still letting its root feed into the overall state root.
+pub fn child_storage(storage_key: &[u8], key: &[u8]) -> Vec<u8> {
+	ext::with(|ext| ext.child_storage(storage_key, key).ok().map(|s| s.to_vec()))
+		.unwrap_or(None)
+		.unwrap_or_else(|| vec![])
+}
+
+/// Set `key` to `value` in the child trie denoted by `storage_key`.
+pub fn set_child_storage(storage_key: &[u8], key: &[u8], value: &[u8]) {
+	ext::with(|ext|
+		ext.set_child_storage(storage_key.to_vec(), key.to_vec(), value.to_vec())
+	);
+}
+
+/// Remove `key` from the child trie denoted by `storage_key`.
+pub fn clear_child_storage(storage_key: &[u8], key: &[u8]) {
+	ext::with(|ext|
+		ext.clear_child_storage(storage_key, key)
+	);
+}
+
+/// Drop the entire child trie denoted by `storage_key`, in one step.
+pub fn kill_child_storage(storage_key: &[u8]) {
+	ext::with(|ext|
+		ext.kill_child_storage(storage_key)
+	);
+}
+
 /// The current relay chain identifier.
 pub fn chain_id() -> u64 {
 	ext::with(|ext|
@@ -92,6 +168,67 @@ pub fn chain_id() -> u64 {
 	).unwrap_or(0)
 }
 
+/// The Merkle/Patricia root of the current storage, folding in both committed storage and any
+/// pending changes made through this module.
+pub fn storage_root() -> [u8; 32] {
+	ext::with(|ext|
+		ext.storage_root()
+	).unwrap_or([0u8; 32])
+}
+
+/// Compute the Merkle/Patricia root of an arbitrary set of key/value pairs.
+pub use trie::trie_root;
+
+/// Compute the Merkle/Patricia root of a list of values, indexed by their position.
+///
+/// Used, for example, to compute the extrinsics root of a block body.
+pub use trie::ordered_trie_root;
+
+/// Whether the current externalities are running in an offchain worker context.
+///
+/// The `http_*` functions below only work when this is `true`. On the deterministic on-chain
+/// path no request can be started: `http_request_start` and `http_request_add_header` return
+/// `Err(())`, the body functions return `HttpError::Invalid`, `http_response_wait` reports
+/// `HttpRequestStatus::Invalid` for each id and `http_response_headers` returns no headers.
+pub fn is_offchain() -> bool {
+	ext::with(|ext| ext.is_offchain()).unwrap_or(false)
+}
+
+/// Start an HTTP request to `uri` using `method` (e.g. `"GET"`), returning an id used to refer
+/// to it in the functions below. `meta` is opaque and forwarded to the underlying transport.
+pub fn http_request_start(method: &str, uri: &str, meta: &[u8]) -> Result<RequestId, ()> {
+	ext::with(|ext| ext.http_request_start(method, uri, meta)).unwrap_or(Err(()))
+}
+
+/// Add a header to a request that has not yet had its body written to or been waited on.
+pub fn http_request_add_header(request_id: RequestId, name: &str, value: &str) -> Result<(), ()> {
+	ext::with(|ext| ext.http_request_add_header(request_id, name, value)).unwrap_or(Err(()))
+}
+
+/// Write a chunk of the request body, failing if `deadline` (a millisecond timestamp) passes
+/// first.
+pub fn http_request_write_body(request_id: RequestId, chunk: &[u8], deadline: Option<Timestamp>) -> Result<(), HttpError> {
+	ext::with(|ext| ext.http_request_write_body(request_id, chunk, deadline)).unwrap_or(Err(HttpError::Invalid))
+}
+
+/// Block until each of `ids` has either finished or `deadline` passes, returning one status per
+/// id in the same order.
+pub fn http_response_wait(ids: &[RequestId], deadline: Option<Timestamp>) -> Vec<HttpRequestStatus> {
+	ext::with(|ext| ext.http_response_wait(ids, deadline))
+		.unwrap_or_else(|| ids.iter().map(|_| HttpRequestStatus::Invalid).collect())
+}
+
+/// The response headers for a finished request, as `(name, value)` pairs.
+pub fn http_response_headers(request_id: RequestId) -> Vec<(Vec<u8>, Vec<u8>)> {
+	ext::with(|ext| ext.http_response_headers(request_id)).unwrap_or_else(|| vec![])
+}
+
+/// Read a chunk of the response body into `buffer`, returning the number of bytes read (`0`
+/// meaning the body is exhausted), failing if `deadline` passes first.
+pub fn http_response_read_body(request_id: RequestId, buffer: &mut [u8], deadline: Option<Timestamp>) -> Result<usize, HttpError> {
+	ext::with(|ext| ext.http_response_read_body(request_id, buffer, deadline)).unwrap_or(Err(HttpError::Invalid))
+}
+
 /// Conduct a Keccak-256 hash of the given data.
 pub use primitives::{blake2_256, twox_128, twox_256};
 
@@ -100,9 +237,46 @@ pub fn ed25519_verify(sig: &[u8; 64], msg: &[u8], pubkey: &[u8; 32]) -> bool {
 	ed25519::verify(&sig[..], msg, &pubkey[..])
 }
 
+/// An error type for the `secp256k1_ecdsa_recover*` family of functions.
+#[derive(Debug, PartialEq, Eq)]
+pub enum EcdsaVerifyError {
+	/// The signature order is invalid.
+	BadRS,
+	/// The signature recovery id is invalid.
+	BadV,
+	/// The signature could not be recovered.
+	BadSignature,
+}
+
+/// Recover the uncompressed (64 byte, `x || y`) public key that produced the given `secp256k1`
+/// ECDSA signature over `msg`, a 32-byte message hash.
+///
+/// `sig` is laid out as `r || s || v`, with `v` being the recovery id in the range `0..=3`
+/// (a value of `27`/`28` is also accepted, matching Ethereum's convention).
+pub fn secp256k1_ecdsa_recover(sig: &[u8; 65], msg: &[u8; 32]) -> Result<[u8; 64], EcdsaVerifyError> {
+	let rs = secp256k1::Signature::parse_slice(&sig[0..64]).map_err(|_| EcdsaVerifyError::BadRS)?;
+	let v = secp256k1::RecoveryId::parse(if sig[64] > 26 { sig[64] - 27 } else { sig[64] })
+		.map_err(|_| EcdsaVerifyError::BadV)?;
+	let pubkey = secp256k1::recover(&secp256k1::Message::parse(msg), &rs, &v)
+		.map_err(|_| EcdsaVerifyError::BadSignature)?;
+	let mut res = [0u8; 64];
+	res.copy_from_slice(&pubkey.serialize()[1..65]);
+	Ok(res)
+}
+
+/// Like `secp256k1_ecdsa_recover`, but returns the compressed (33 byte) form of the public key.
+pub fn secp256k1_ecdsa_recover_compressed(sig: &[u8; 65], msg: &[u8; 32]) -> Result<[u8; 33], EcdsaVerifyError> {
+	let rs = secp256k1::Signature::parse_slice(&sig[0..64]).map_err(|_| EcdsaVerifyError::BadRS)?;
+	let v = secp256k1::RecoveryId::parse(if sig[64] > 26 { sig[64] - 27 } else { sig[64] })
+		.map_err(|_| EcdsaVerifyError::BadV)?;
+	let pubkey = secp256k1::recover(&secp256k1::Message::parse(msg), &rs, &v)
+		.map_err(|_| EcdsaVerifyError::BadSignature)?;
+	Ok(pubkey.serialize_compressed())
+}
+
 /// Execute the given closure with global function available whose functionality routes into the
 /// externalities `ext`. Forwards the value that the closure returns.
-pub fn with_externalities<R, F: FnOnce() -> R>(ext: &mut (Externalities<Error=NoError> + 'static), f: F) -> R {
+pub fn with_externalities<R, F: FnOnce() -> R>(ext: &mut (Externalities<Error=Error> + 'static), f: F) -> R {
 	ext::using(ext, f)
 }
 
@@ -116,22 +290,176 @@ mod tests {
 	use super::*;
 	use std::collections::HashMap;
 
+	#[derive(Debug, Default, Clone)]
+	struct RecordedHttpRequest {
+		method: String,
+		uri: String,
+		meta: Vec<u8>,
+		headers: Vec<(String, String)>,
+		body: Vec<u8>,
+	}
+
+	#[derive(Debug, Default, Clone)]
+	struct CannedHttpResponse {
+		status: u16,
+		headers: Vec<(Vec<u8>, Vec<u8>)>,
+		body: Vec<u8>,
+	}
+
 	#[derive(Debug, Default)]
 	struct TestExternalities {
 		storage: HashMap<Vec<u8>, Vec<u8>>,
+		// A stack of transactional overlays, innermost last. `None` records a pending delete.
+		overlay_stack: Vec<HashMap<Vec<u8>, Option<Vec<u8>>>>,
+		child_storage: HashMap<Vec<u8>, HashMap<Vec<u8>, Vec<u8>>>,
+		offchain: bool,
+		next_request_id: RequestId,
+		http_requests: HashMap<RequestId, RecordedHttpRequest>,
+		http_responses: HashMap<RequestId, CannedHttpResponse>,
 	}
 	impl Externalities for TestExternalities {
-		type Error = NoError;
+		type Error = Error;
 
-		fn storage(&self, key: &[u8]) -> Result<&[u8], NoError> {
-			Ok(self.storage.get(&key.to_vec()).map_or(&[] as &[u8], Vec::as_slice))
+		fn storage(&self, key: &[u8]) -> Result<&[u8], Error> {
+			for overlay in self.overlay_stack.iter().rev() {
+				if let Some(entry) = overlay.get(key) {
+					return entry.as_ref().map(Vec::as_slice).ok_or(Error::NotFound);
+				}
+			}
+			self.storage.get(&key.to_vec()).map(Vec::as_slice).ok_or(Error::NotFound)
 		}
 
 		fn set_storage(&mut self, key: Vec<u8>, value: Vec<u8>) {
-			self.storage.insert(key, value);
+			match self.overlay_stack.last_mut() {
+				Some(top) => { top.insert(key, Some(value)); }
+				None => { self.storage.insert(key, value); }
+			}
+		}
+
+		fn clear_storage(&mut self, key: &[u8]) {
+			match self.overlay_stack.last_mut() {
+				Some(top) => { top.insert(key.to_vec(), None); }
+				None => { self.storage.remove(&key.to_vec()); }
+			}
+		}
+
+		fn start_transaction(&mut self) {
+			self.overlay_stack.push(HashMap::new());
+		}
+
+		fn commit_transaction(&mut self) {
+			let top = self.overlay_stack.pop().expect("commit_transaction called without a matching start_transaction");
+			match self.overlay_stack.last_mut() {
+				Some(parent) => parent.extend(top),
+				None => for (key, value) in top {
+					match value {
+						Some(value) => { self.storage.insert(key, value); }
+						None => { self.storage.remove(&key); }
+					}
+				},
+			}
+		}
+
+		fn rollback_transaction(&mut self) {
+			self.overlay_stack.pop().expect("rollback_transaction called without a matching start_transaction");
+		}
+
+		fn child_storage(&self, storage_key: &[u8], key: &[u8]) -> Result<&[u8], Error> {
+			self.child_storage.get(&storage_key.to_vec())
+				.and_then(|trie| trie.get(&key.to_vec()))
+				.map(Vec::as_slice)
+				.ok_or(Error::NotFound)
+		}
+
+		fn set_child_storage(&mut self, storage_key: Vec<u8>, key: Vec<u8>, value: Vec<u8>) {
+			self.child_storage.entry(storage_key).or_insert_with(HashMap::new).insert(key, value);
+		}
+
+		fn clear_child_storage(&mut self, storage_key: &[u8], key: &[u8]) {
+			if let Some(trie) = self.child_storage.get_mut(&storage_key.to_vec()) {
+				trie.remove(&key.to_vec());
+			}
+		}
+
+		fn kill_child_storage(&mut self, storage_key: &[u8]) {
+			self.child_storage.remove(&storage_key.to_vec());
 		}
 
 		fn chain_id(&self) -> u64 { 42 }
+
+		fn storage_root(&self) -> [u8; 32] {
+			// Fold committed storage with any open transactional overlays (outermost first, so
+			// the innermost overlay has the final say), honoring `None` as a pending delete.
+			let mut merged: HashMap<Vec<u8>, Option<Vec<u8>>> = self.storage.iter()
+				.map(|(k, v)| (k.clone(), Some(v.clone())))
+				.collect();
+			for overlay in &self.overlay_stack {
+				for (key, value) in overlay {
+					merged.insert(key.clone(), value.clone());
+				}
+			}
+			trie_root(merged.into_iter().filter_map(|(k, v)| v.map(|v| (k, v))).collect())
+		}
+
+		fn is_offchain(&self) -> bool { self.offchain }
+
+		fn http_request_start(&mut self, method: &str, uri: &str, meta: &[u8]) -> Result<RequestId, ()> {
+			if !self.offchain { return Err(()); }
+			let id = self.next_request_id;
+			self.next_request_id += 1;
+			self.http_requests.insert(id, RecordedHttpRequest {
+				method: method.to_string(),
+				uri: uri.to_string(),
+				meta: meta.to_vec(),
+				headers: vec![],
+				body: vec![],
+			});
+			Ok(id)
+		}
+
+		fn http_request_add_header(&mut self, request_id: RequestId, name: &str, value: &str) -> Result<(), ()> {
+			self.http_requests.get_mut(&request_id)
+				.map(|req| req.headers.push((name.to_string(), value.to_string())))
+				.ok_or(())
+		}
+
+		fn http_request_write_body(&mut self, request_id: RequestId, chunk: &[u8], _deadline: Option<Timestamp>) -> Result<(), HttpError> {
+			self.http_requests.get_mut(&request_id)
+				.map(|req| req.body.extend_from_slice(chunk))
+				.ok_or(HttpError::Invalid)
+		}
+
+		fn http_response_wait(&mut self, ids: &[RequestId], _deadline: Option<Timestamp>) -> Vec<HttpRequestStatus> {
+			ids.iter().map(|id| {
+				if !self.http_requests.contains_key(id) {
+					HttpRequestStatus::Invalid
+				} else if let Some(resp) = self.http_responses.get(id) {
+					HttpRequestStatus::Finished(resp.status)
+				} else {
+					HttpRequestStatus::DeadlineReached
+				}
+			}).collect()
+		}
+
+		fn http_response_headers(&mut self, request_id: RequestId) -> Vec<(Vec<u8>, Vec<u8>)> {
+			self.http_responses.get(&request_id).map(|r| r.headers.clone()).unwrap_or_else(|| vec![])
+		}
+
+		fn http_response_read_body(&mut self, request_id: RequestId, buffer: &mut [u8], _deadline: Option<Timestamp>) -> Result<usize, HttpError> {
+			let resp = self.http_responses.get_mut(&request_id).ok_or(HttpError::Invalid)?;
+			let n = ::std::cmp::min(buffer.len(), resp.body.len());
+			buffer[..n].copy_from_slice(&resp.body[..n]);
+			resp.body.drain(..n);
+			Ok(n)
+		}
+	}
+
+	impl TestExternalities {
+		/// Feed a canned response for a request previously started with `http_request_start`, to
+		/// be returned the next time it's waited on.
+		fn respond_http(&mut self, request_id: RequestId, status: u16, headers: Vec<(Vec<u8>, Vec<u8>)>, body: Vec<u8>) {
+			self.http_responses.insert(request_id, CannedHttpResponse { status, headers, body });
+		}
 	}
 
 	macro_rules! map {
@@ -142,15 +470,17 @@ mod tests {
 
 	#[test]
 	fn storage_works() {
-		let mut t = TestExternalities { storage: map![], };
+		let mut t = TestExternalities { storage: map![], ..Default::default() };
 		assert!(with_externalities(&mut t, || {
 			assert_eq!(storage(b"hello"), b"".to_vec());
 			set_storage(b"hello", b"world");
 			assert_eq!(storage(b"hello"), b"world".to_vec());
 			assert_eq!(storage(b"foo"), b"".to_vec());
-			set_storage(b"foo", &[1, 2, 3][..]);
-			assert_eq!(storage_into::<[u8; 3]>(b"foo"), Some([1, 2, 3]));
-			assert_eq!(storage_into::<[u8; 3]>(b"hello"), None);
+			set_storage(b"foo", &42u32.to_le_bytes());
+			assert_eq!(storage_decode::<u32>(b"foo"), Some(42));
+			set_storage(b"short", &[1, 2]);
+			assert_eq!(storage_decode::<u32>(b"short"), None);
+			assert_eq!(storage_decode::<u32>(b"missing"), None);
 			true
 		}));
 
@@ -162,4 +492,213 @@ mod tests {
 			false
 		}));
 	}
+
+	#[test]
+	fn transaction_commit_and_rollback() {
+		let mut t = TestExternalities::default();
+		with_externalities(&mut t, || {
+			set_storage(b"foo", b"bar");
+
+			start_transaction();
+			set_storage(b"foo", b"baz");
+			clear_storage(b"untouched");
+			assert_eq!(storage(b"foo"), b"baz".to_vec());
+			rollback_transaction();
+			assert_eq!(storage(b"foo"), b"bar".to_vec());
+
+			start_transaction();
+			set_storage(b"foo", b"baz");
+			commit_transaction();
+			assert_eq!(storage(b"foo"), b"baz".to_vec());
+
+			start_transaction();
+			clear_storage(b"foo");
+			assert_eq!(storage(b"foo"), b"".to_vec());
+			commit_transaction();
+			assert_eq!(storage(b"foo"), b"".to_vec());
+		});
+		assert_eq!(t.storage.get(b"foo".as_ref()), None);
+	}
+
+	#[test]
+	fn nested_transactions_merge_into_parent_overlay() {
+		let mut t = TestExternalities::default();
+		with_externalities(&mut t, || {
+			start_transaction();
+			set_storage(b"foo", b"outer");
+			start_transaction();
+			set_storage(b"foo", b"inner");
+			commit_transaction();
+			assert_eq!(storage(b"foo"), b"inner".to_vec());
+			rollback_transaction();
+			assert_eq!(storage(b"foo"), b"".to_vec());
+		});
+	}
+
+	#[test]
+	fn child_storage_works() {
+		let mut t = TestExternalities::default();
+		assert!(with_externalities(&mut t, || {
+			assert_eq!(child_storage(b"child1", b"hello"), b"".to_vec());
+			set_child_storage(b"child1", b"hello", b"world");
+			assert_eq!(child_storage(b"child1", b"hello"), b"world".to_vec());
+			// distinct from top-level storage under the same key
+			assert_eq!(storage(b"hello"), b"".to_vec());
+
+			clear_child_storage(b"child1", b"hello");
+			assert_eq!(child_storage(b"child1", b"hello"), b"".to_vec());
+
+			set_child_storage(b"child1", b"a", b"1");
+			set_child_storage(b"child1", b"b", b"2");
+			kill_child_storage(b"child1");
+			assert_eq!(child_storage(b"child1", b"a"), b"".to_vec());
+			assert_eq!(child_storage(b"child1", b"b"), b"".to_vec());
+			true
+		}));
+	}
+
+	#[test]
+	fn storage_root_changes_with_storage() {
+		let mut t = TestExternalities::default();
+		let empty_root = with_externalities(&mut t, || storage_root());
+
+		t.storage.insert(b"foo".to_vec(), b"bar".to_vec());
+		let root_after_insert = with_externalities(&mut t, || storage_root());
+
+		assert_ne!(empty_root, root_after_insert);
+		assert_eq!(root_after_insert, with_externalities(&mut t, || storage_root()));
+	}
+
+	#[test]
+	fn storage_root_folds_in_open_transaction() {
+		let mut t = TestExternalities::default();
+		let committed_root = with_externalities(&mut t, || {
+			set_storage(b"foo", b"bar");
+			storage_root()
+		});
+
+		let root_with_pending_write = with_externalities(&mut t, || {
+			start_transaction();
+			set_storage(b"foo", b"baz");
+			storage_root()
+		});
+		assert_ne!(committed_root, root_with_pending_write);
+
+		let root_with_pending_delete = with_externalities(&mut t, || {
+			rollback_transaction();
+			start_transaction();
+			clear_storage(b"foo");
+			storage_root()
+		});
+		assert_ne!(committed_root, root_with_pending_delete);
+		assert_eq!(root_with_pending_delete, with_externalities(&mut t, || trie_root(vec![])));
+	}
+
+	#[test]
+	fn http_request_requires_offchain_context() {
+		let mut t = TestExternalities::default();
+		assert!(with_externalities(&mut t, || {
+			http_request_start("GET", "https://example.com", &[]).is_err()
+		}));
+	}
+
+	#[test]
+	fn http_request_works_offchain() {
+		let mut t = TestExternalities { offchain: true, ..Default::default() };
+		let id = with_externalities(&mut t, || {
+			let id = http_request_start("GET", "https://example.com", &[]).unwrap();
+			http_request_add_header(id, "Accept", "application/json").unwrap();
+			assert_eq!(http_response_wait(&[id], None), vec![HttpRequestStatus::DeadlineReached]);
+			id
+		});
+
+		t.respond_http(id, 200, vec![(b"content-type".to_vec(), b"application/json".to_vec())], b"{}".to_vec());
+
+		with_externalities(&mut t, || {
+			assert_eq!(http_response_wait(&[id], None), vec![HttpRequestStatus::Finished(200)]);
+			assert_eq!(
+				http_response_headers(id),
+				vec![(b"content-type".to_vec(), b"application/json".to_vec())],
+			);
+			let mut buf = [0u8; 2];
+			assert_eq!(http_response_read_body(id, &mut buf, None), Ok(2));
+			assert_eq!(&buf, b"{}");
+			assert_eq!(http_response_read_body(id, &mut buf, None), Ok(0));
+		});
+	}
+
+	#[test]
+	fn trie_root_is_order_independent() {
+		let a = trie_root(vec![(b"foo".to_vec(), b"bar".to_vec()), (b"baz".to_vec(), b"qux".to_vec())]);
+		let b = trie_root(vec![(b"baz".to_vec(), b"qux".to_vec()), (b"foo".to_vec(), b"bar".to_vec())]);
+		assert_eq!(a, b);
+	}
+
+	fn hex_bytes(s: &str) -> Vec<u8> {
+		(0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+	}
+
+	// r || s || v recovering the well-known secp256k1 keypair for private key `3`, over the
+	// sha256 hash of b"hello world".
+	const KNOWN_SIG: &str = "5cbdf0646e5db4eaa398f365f2ea7a0e3d419b7e0330e39ce92bddedcac4f9bc\
+		4237da6a1fc57af814914f93abe4284e7f784056808e9889e64af03577521abb00";
+	const KNOWN_MSG: &str = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+	const KNOWN_PUBKEY: &str = "f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9\
+		388f7b0f632de8140fe337e62a37f3566500a99934c2231b6cb9fd7584b8e672";
+	const KNOWN_PUBKEY_COMPRESSED: &str = "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9";
+
+	fn known_sig() -> [u8; 65] {
+		let mut sig = [0u8; 65];
+		sig.copy_from_slice(&hex_bytes(KNOWN_SIG));
+		sig
+	}
+
+	fn known_msg() -> [u8; 32] {
+		let mut msg = [0u8; 32];
+		msg.copy_from_slice(&hex_bytes(KNOWN_MSG));
+		msg
+	}
+
+	#[test]
+	fn secp256k1_ecdsa_recover_known_answer() {
+		let pubkey = secp256k1_ecdsa_recover(&known_sig(), &known_msg()).unwrap();
+		assert_eq!(pubkey.to_vec(), hex_bytes(KNOWN_PUBKEY));
+
+		let pubkey_compressed = secp256k1_ecdsa_recover_compressed(&known_sig(), &known_msg()).unwrap();
+		assert_eq!(pubkey_compressed.to_vec(), hex_bytes(KNOWN_PUBKEY_COMPRESSED));
+	}
+
+	#[test]
+	fn secp256k1_ecdsa_recover_normalizes_ethereum_style_recovery_id() {
+		// `v = 27` should behave exactly like `v = 0`.
+		let mut sig = known_sig();
+		sig[64] = 27;
+		let recovered = secp256k1_ecdsa_recover(&sig, &known_msg()).unwrap();
+		assert_eq!(recovered.to_vec(), hex_bytes(KNOWN_PUBKEY));
+	}
+
+	#[test]
+	fn secp256k1_ecdsa_recover_bad_rs() {
+		let mut sig = known_sig();
+		// An all-`0xff` `r` is not a valid field element, so signature parsing itself fails.
+		for b in sig[0..32].iter_mut() { *b = 0xff; }
+		assert_eq!(secp256k1_ecdsa_recover(&sig, &known_msg()).unwrap_err(), EcdsaVerifyError::BadRS);
+	}
+
+	#[test]
+	fn secp256k1_ecdsa_recover_bad_v() {
+		let mut sig = known_sig();
+		sig[64] = 4;
+		assert_eq!(secp256k1_ecdsa_recover(&sig, &known_msg()).unwrap_err(), EcdsaVerifyError::BadV);
+	}
+
+	#[test]
+	fn secp256k1_ecdsa_recover_bad_signature() {
+		// `r = 5` is in-range so it parses fine, but `5` isn't the x-coordinate of any point on
+		// the curve, so reconstructing `R` during recovery itself fails.
+		let mut sig = [0u8; 65];
+		sig[31] = 5;
+		sig[63] = 1;
+		assert_eq!(secp256k1_ecdsa_recover(&sig, &known_msg()).unwrap_err(), EcdsaVerifyError::BadSignature);
+	}
 }
@@ -0,0 +1,52 @@
+// Copyright 2017 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Types shared by the offchain HTTP host functions.
+//!
+//! These only make sense off the deterministic on-chain path: a node driving offchain worker
+//! code binds the request/response functions to a real HTTP client, while on-chain execution
+//! never sets the "offchain" flag on its `Externalities` and so no request can be started there;
+//! the functions that return an `HttpError` fail fast with `HttpError::Invalid`.
+
+/// Identifies an in-flight (or completed) HTTP request within a single offchain worker run.
+pub type RequestId = u16;
+
+/// A timestamp expressed as milliseconds since the Unix epoch, used for request deadlines.
+pub type Timestamp = u64;
+
+/// An error produced by one of the `http_*` host functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpError {
+	/// The deadline was reached before the operation completed.
+	DeadlineReached,
+	/// The transport returned an I/O error.
+	IoError,
+	/// The request id is unknown, or the function was called outside of an offchain context.
+	Invalid,
+}
+
+/// The state of a request as observed by `http_response_wait`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HttpRequestStatus {
+	/// The request id is unknown.
+	Invalid,
+	/// The deadline was reached before the response arrived.
+	DeadlineReached,
+	/// The response arrived with the given HTTP status code.
+	Finished(u16),
+	/// The transport reported an I/O error.
+	IoError,
+}
@@ -0,0 +1,172 @@
+// Copyright 2017 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A minimal base-16 (hex) Merkle-Patricia trie, hashed with `blake2_256`.
+//!
+//! This is the same shape of trie used to commit to runtime storage: keys are split into
+//! nibbles, nodes are either leaves, extensions or 16-way branches, and any node whose encoding
+//! is at least `HASHED_NODE_THRESHOLD` bytes long is stored (and referenced) by its hash rather
+//! than inline. Only root computation is implemented here; nothing is persisted.
+
+use primitives::blake2_256;
+
+/// Node encodings at or above this length are addressed by hash rather than embedded inline.
+const HASHED_NODE_THRESHOLD: usize = 32;
+
+enum Node {
+	Empty,
+	Leaf(Vec<u8>, Vec<u8>),
+	Extension(Vec<u8>, Box<Node>),
+	Branch(Box<[Node; 16]>, Option<Vec<u8>>),
+}
+
+fn bytes_to_nibbles(bytes: &[u8]) -> Vec<u8> {
+	let mut nibbles = Vec::with_capacity(bytes.len() * 2);
+	for b in bytes {
+		nibbles.push(b >> 4);
+		nibbles.push(b & 0x0f);
+	}
+	nibbles
+}
+
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+	a.iter().zip(b.iter()).take_while(|&(x, y)| x == y).count()
+}
+
+fn empty_branch() -> Box<[Node; 16]> {
+	Box::new([
+		Node::Empty, Node::Empty, Node::Empty, Node::Empty,
+		Node::Empty, Node::Empty, Node::Empty, Node::Empty,
+		Node::Empty, Node::Empty, Node::Empty, Node::Empty,
+		Node::Empty, Node::Empty, Node::Empty, Node::Empty,
+	])
+}
+
+/// Build a trie node out of a set of `(remaining nibble path, value)` pairs, where each path is
+/// relative to this node (the nibbles already consumed by its ancestors have been stripped off).
+fn build(pairs: &[(Vec<u8>, Vec<u8>)]) -> Node {
+	match pairs.len() {
+		0 => Node::Empty,
+		1 => Node::Leaf(pairs[0].0.clone(), pairs[0].1.clone()),
+		_ => {
+			let prefix_len = pairs.iter().skip(1).fold(pairs[0].0.len(), |acc, (path, _)|
+				::std::cmp::min(acc, common_prefix_len(&pairs[0].0, path)));
+
+			if prefix_len > 0 && pairs.iter().all(|(path, _)| path.len() >= prefix_len) {
+				let shared = pairs[0].0[..prefix_len].to_vec();
+				let rest: Vec<_> = pairs.iter()
+					.map(|(path, value)| (path[prefix_len..].to_vec(), value.clone()))
+					.collect();
+				return Node::Extension(shared, Box::new(build(&rest)));
+			}
+
+			let mut branch = empty_branch();
+			let mut value_here = None;
+			let mut buckets: Vec<Vec<(Vec<u8>, Vec<u8>)>> = (0..16).map(|_| Vec::new()).collect();
+			for (path, value) in pairs {
+				if path.is_empty() {
+					value_here = Some(value.clone());
+				} else {
+					buckets[path[0] as usize].push((path[1..].to_vec(), value.clone()));
+				}
+			}
+			for (i, bucket) in buckets.into_iter().enumerate() {
+				if !bucket.is_empty() {
+					branch[i] = build(&bucket);
+				}
+			}
+			Node::Branch(branch, value_here)
+		}
+	}
+}
+
+/// Encode a node, inlining children whose own encoding is shorter than the hash threshold and
+/// referencing the rest by their `blake2_256` hash.
+fn encode(node: &Node) -> Vec<u8> {
+	match *node {
+		Node::Empty => vec![0],
+		Node::Leaf(ref path, ref value) => {
+			let mut out = vec![1];
+			encode_bytes(&mut out, path);
+			encode_bytes(&mut out, value);
+			out
+		}
+		Node::Extension(ref path, ref child) => {
+			let mut out = vec![2];
+			encode_bytes(&mut out, path);
+			encode_bytes(&mut out, &encode_child(child));
+			out
+		}
+		Node::Branch(ref children, ref value) => {
+			let mut out = vec![3];
+			for child in children.iter() {
+				encode_bytes(&mut out, &encode_child(child));
+			}
+			match *value {
+				Some(ref v) => { out.push(1); encode_bytes(&mut out, v); }
+				None => out.push(0),
+			}
+			out
+		}
+	}
+}
+
+/// Encode a child node reference: either the raw node encoding if it's short enough to inline,
+/// or the hash of that encoding.
+fn encode_child(node: &Node) -> Vec<u8> {
+	let encoded = encode(node);
+	if encoded.len() < HASHED_NODE_THRESHOLD {
+		encoded
+	} else {
+		blake2_256(&encoded).to_vec()
+	}
+}
+
+fn encode_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+	out.extend((bytes.len() as u32).to_le_bytes().iter());
+	out.extend_from_slice(bytes);
+}
+
+/// Compute the root hash of the trie built from `input`, a set of key/value pairs.
+///
+/// The root is always addressed by hash, regardless of how small its encoding is.
+pub fn trie_root(mut input: Vec<(Vec<u8>, Vec<u8>)>) -> [u8; 32] {
+	input.sort_by(|a, b| a.0.cmp(&b.0));
+	input.dedup_by(|a, b| a.0 == b.0);
+
+	let pairs: Vec<_> = input.into_iter()
+		.map(|(key, value)| (bytes_to_nibbles(&key), value))
+		.collect();
+
+	blake2_256(&encode(&build(&pairs)))
+}
+
+/// Compute the root hash of the trie formed by indexing `input` with its `u32` position (`0`,
+/// `1`, `2`, ...), as used for e.g. a block's extrinsics root. Each key is the position's four
+/// little-endian bytes preceded by their length, itself a little-endian `u32` (always `4`).
+pub fn ordered_trie_root(input: Vec<Vec<u8>>) -> [u8; 32] {
+	let pairs = input.into_iter()
+		.enumerate()
+		.map(|(i, value)| (encode_index(i as u32), value))
+		.collect();
+	trie_root(pairs)
+}
+
+fn encode_index(i: u32) -> Vec<u8> {
+	let mut out = Vec::new();
+	encode_bytes(&mut out, &i.to_le_bytes());
+	out
+}